@@ -0,0 +1,42 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+use music_player_types::types::{Device, AIRPLAY_SERVICE_NAME, CHROMECAST_SERVICE_NAME};
+
+#[cfg(test)]
+mod tests;
+
+/// How long to listen for mDNS responses before returning whatever was found.
+const SCAN_WINDOW: Duration = Duration::from_secs(2);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScanError {
+    #[error("failed to start mDNS daemon: {0}")]
+    Daemon(#[from] mdns_sd::Error),
+}
+
+/// Browses for AirPlay and Chromecast devices on the local network for
+/// `SCAN_WINDOW` and returns whatever was discovered.
+pub async fn scan_devices() -> Result<Arc<Mutex<Vec<Device>>>, ScanError> {
+    let daemon = ServiceDaemon::new()?;
+    let devices = Arc::new(Mutex::new(Vec::new()));
+
+    for service in [AIRPLAY_SERVICE_NAME, CHROMECAST_SERVICE_NAME] {
+        let receiver = daemon.browse(service)?;
+        let devices = Arc::clone(&devices);
+
+        tokio::spawn(async move {
+            while let Ok(event) = receiver.recv_async().await {
+                if let ServiceEvent::ServiceResolved(info) = event {
+                    devices.lock().unwrap().push(Device::from(info));
+                }
+            }
+        });
+    }
+
+    tokio::time::sleep(SCAN_WINDOW).await;
+    let _ = daemon.shutdown();
+
+    Ok(devices)
+}
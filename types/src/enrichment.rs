@@ -0,0 +1,261 @@
+//! Background MusicBrainz enrichment daemon.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+
+use serde::Deserialize;
+
+use crate::matching::score_candidate;
+use crate::types::{Album, Artist, Song};
+
+/// A local entity awaiting MusicBrainz reconciliation.
+#[derive(Debug, Clone)]
+pub enum Entity {
+    Artist(Artist),
+    Album(Album),
+    Song(Song),
+}
+
+/// Sent from the indexer to the enrichment daemon.
+#[derive(Debug, Clone)]
+pub struct EnrichRequest {
+    /// Echoed back on the matching `EnrichResult` so a caller with several
+    /// requests in flight can tell which entity a result answers.
+    pub id: u64,
+    pub entity: Entity,
+}
+
+/// A single MusicBrainz hit for an `EnrichRequest`.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub mbid: String,
+    pub score: f32,
+}
+
+/// Sent back from the daemon once a request has been resolved.
+#[derive(Debug, Clone)]
+pub struct EnrichResult {
+    /// Matches the `EnrichRequest::id` this result answers.
+    pub id: u64,
+    pub mbid: Option<String>,
+    pub score: f32,
+    pub candidates: Vec<Candidate>,
+}
+
+/// Queries MusicBrainz for candidates matching a local entity. Implemented
+/// by the HTTP client in production and by a stub in tests.
+pub trait MusicBrainzClient: Send {
+    fn lookup(&self, entity: &Entity) -> Vec<Candidate>;
+}
+
+/// Handle to the running enrichment daemon, spawned once at startup.
+pub struct EnrichmentDaemon {
+    requests: Sender<EnrichRequest>,
+    results: Receiver<EnrichResult>,
+    handle: JoinHandle<()>,
+    next_id: AtomicU64,
+}
+
+impl EnrichmentDaemon {
+    /// Spawns the daemon thread, which owns `client` for the rest of the
+    /// process and services requests until its sender is dropped.
+    pub fn spawn(client: impl MusicBrainzClient + 'static) -> Self {
+        let (request_tx, request_rx) = mpsc::channel::<EnrichRequest>();
+        let (result_tx, result_rx) = mpsc::channel::<EnrichResult>();
+
+        let handle = thread::spawn(move || {
+            while let Ok(request) = request_rx.recv() {
+                let mut candidates = client.lookup(&request.entity);
+                candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+                let best = candidates.first();
+                let result = EnrichResult {
+                    id: request.id,
+                    mbid: best.map(|candidate| candidate.mbid.clone()),
+                    score: best.map(|candidate| candidate.score).unwrap_or(0.0),
+                    candidates,
+                };
+
+                if result_tx.send(result).is_err() {
+                    break;
+                }
+            }
+        });
+
+        EnrichmentDaemon {
+            requests: request_tx,
+            results: result_rx,
+            handle,
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Queues an entity for reconciliation and returns a request id that the
+    /// matching `EnrichResult` will echo back, so callers with several
+    /// requests in flight can tell results apart. Never blocks on network I/O.
+    pub fn enrich(&self, entity: Entity) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let _ = self.requests.send(EnrichRequest { id, entity });
+        id
+    }
+
+    /// Non-blocking poll for a completed enrichment. The indexer should call
+    /// this from its main loop: `Empty` means keep working, `Disconnected`
+    /// means the daemon has shut down.
+    pub fn try_recv(&self) -> Result<EnrichResult, TryRecvError> {
+        self.results.try_recv()
+    }
+
+    /// Drops the request channel and waits for the daemon thread to exit.
+    pub fn join(self) -> thread::Result<()> {
+        drop(self.requests);
+        self.handle.join()
+    }
+}
+
+/// Falls back to the MD5-derived local id when no MusicBrainz match was found.
+pub fn resolve_id(local_id: impl AsRef<str>, result: &EnrichResult) -> String {
+    result
+        .mbid
+        .clone()
+        .unwrap_or_else(|| local_id.as_ref().to_string())
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct MusicBrainzSearchResponse {
+    #[serde(default)]
+    artists: Vec<MusicBrainzHit>,
+    #[serde(default)]
+    releases: Vec<MusicBrainzHit>,
+    #[serde(default)]
+    recordings: Vec<MusicBrainzHit>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct MusicBrainzHit {
+    id: String,
+    #[serde(default)]
+    score: f32,
+    /// Populated on artist hits.
+    #[serde(default)]
+    name: String,
+    /// Populated on release/recording hits.
+    #[serde(default)]
+    title: String,
+    #[serde(rename = "artist-credit", default)]
+    artist_credit: Vec<MusicBrainzArtistCredit>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct MusicBrainzArtistCredit {
+    name: String,
+}
+
+/// Queries the MusicBrainz search API over HTTP. Runs on the daemon thread,
+/// so it uses a blocking client rather than pulling in an async runtime.
+pub struct HttpMusicBrainzClient {
+    base_url: String,
+    client: reqwest::blocking::Client,
+}
+
+impl HttpMusicBrainzClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        HttpMusicBrainzClient {
+            base_url: base_url.into(),
+            client: reqwest::blocking::Client::new(),
+        }
+    }
+
+    fn search_term(entity: &Entity) -> (&'static str, String) {
+        match entity {
+            Entity::Artist(artist) => ("artist", artist.name.clone()),
+            Entity::Album(album) => (
+                "release",
+                format!("{} AND artist:{}", album.title, album.artist),
+            ),
+            Entity::Song(song) => (
+                "recording",
+                format!("{} AND artist:{}", song.title, song.artist),
+            ),
+        }
+    }
+
+    /// The title/artist this entity is searched under, for scoring hits
+    /// against with [`score_candidate`].
+    fn query_fields(entity: &Entity) -> (&str, &str) {
+        match entity {
+            Entity::Artist(artist) => (artist.name.as_str(), ""),
+            Entity::Album(album) => (album.title.as_str(), album.artist.as_str()),
+            Entity::Song(song) => (song.title.as_str(), song.artist.as_str()),
+        }
+    }
+
+    /// The title/artist a hit carries, mirroring `query_fields` so the two
+    /// can be compared by [`score_candidate`].
+    fn hit_fields(entity: &Entity, hit: &MusicBrainzHit) -> (String, String) {
+        match entity {
+            Entity::Artist(_) => (hit.name.clone(), String::new()),
+            Entity::Album(_) | Entity::Song(_) => {
+                let artist = hit
+                    .artist_credit
+                    .iter()
+                    .map(|credit| credit.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                (hit.title.clone(), artist)
+            }
+        }
+    }
+}
+
+impl MusicBrainzClient for HttpMusicBrainzClient {
+    fn lookup(&self, entity: &Entity) -> Vec<Candidate> {
+        let (endpoint, query) = Self::search_term(entity);
+        let url = format!("{}/{endpoint}", self.base_url);
+
+        let response = match self
+            .client
+            .get(url)
+            .query(&[("query", query.as_str()), ("fmt", "json")])
+            .send()
+        {
+            Ok(response) => response,
+            Err(_) => return Vec::new(),
+        };
+
+        let parsed: MusicBrainzSearchResponse = match response.json() {
+            Ok(parsed) => parsed,
+            Err(_) => return Vec::new(),
+        };
+
+        let hits = match entity {
+            Entity::Artist(_) => parsed.artists,
+            Entity::Album(_) => parsed.releases,
+            Entity::Song(_) => parsed.recordings,
+        };
+
+        let (query_title, query_artist) = Self::query_fields(entity);
+
+        hits.into_iter()
+            .map(|hit| {
+                let (hit_title, hit_artist) = Self::hit_fields(entity, &hit);
+                // MusicBrainz scores are 0-100; normalize to [0, 1] to use as
+                // the popularity tie-breaker in `score_candidate`.
+                let popularity = hit.score / 100.0;
+                let score = score_candidate(
+                    query_title,
+                    query_artist,
+                    &hit_title,
+                    &hit_artist,
+                    popularity,
+                );
+
+                Candidate {
+                    mbid: hit.id,
+                    score,
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,104 @@
+//! Strongly-typed entity ids.
+//!
+//! Every entity used to carry a bare `String` id produced by an MD5 hash,
+//! which meant nothing stopped an album id from being passed where an artist
+//! id was expected. These newtypes make that a compile error while keeping
+//! the underlying representation (and its `tantivy`/tag round-trip) the same
+//! plain hash string. `*IdRef` borrows the hash for query construction so
+//! lookups don't need to clone an owned id just to compare it.
+
+use std::fmt;
+
+macro_rules! define_id {
+    ($name:ident, $ref_name:ident) => {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn from_md5(input: &str) -> Self {
+                Self(format!("{:x}", md5::compute(input)))
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(id: String) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(id: &str) -> Self {
+                Self(id.to_string())
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl PartialEq<String> for $name {
+            fn eq(&self, other: &String) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl PartialEq<$name> for String {
+            fn eq(&self, other: &$name) -> bool {
+                *self == other.0
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl PartialEq<$name> for &str {
+            fn eq(&self, other: &$name) -> bool {
+                *self == other.0
+            }
+        }
+
+        #[doc = concat!("A borrowed, allocation-free view of an [`", stringify!($name), "`].")]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub struct $ref_name<'a>(&'a str);
+
+        impl<'a> $ref_name<'a> {
+            pub fn new(id: &'a str) -> Self {
+                Self(id)
+            }
+        }
+
+        impl<'a> From<&'a $name> for $ref_name<'a> {
+            fn from(id: &'a $name) -> Self {
+                Self(&id.0)
+            }
+        }
+
+        impl fmt::Display for $ref_name<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl AsRef<str> for $ref_name<'_> {
+            fn as_ref(&self) -> &str {
+                self.0
+            }
+        }
+    };
+}
+
+define_id!(ArtistId, ArtistIdRef);
+define_id!(AlbumId, AlbumIdRef);
+define_id!(SongId, SongIdRef);
+define_id!(CoverId, CoverIdRef);
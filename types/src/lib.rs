@@ -0,0 +1,8 @@
+pub mod enrichment;
+pub mod ids;
+pub mod matching;
+pub mod source;
+pub mod types;
+
+#[cfg(test)]
+mod tests;
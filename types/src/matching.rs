@@ -0,0 +1,128 @@
+//! Ranks MusicBrainz candidates and resolves ambiguous matches.
+
+use lofty::{ItemKey, ItemValue, Tag, TagItem};
+
+use crate::enrichment::{Candidate, EnrichResult};
+use crate::ids::{AlbumId, ArtistId, SongId};
+use crate::types::{Album, Artist, Song};
+
+/// Minimum score a top candidate must clear to be auto-accepted.
+pub const AUTO_ACCEPT_THRESHOLD: f32 = 0.85;
+/// Minimum lead the top candidate must have over the runner-up to be
+/// auto-accepted; otherwise the match is too close to call automatically.
+pub const AUTO_ACCEPT_MARGIN: f32 = 0.15;
+
+/// The result of comparing a `Candidate` list against the thresholds above.
+#[derive(Debug, Clone)]
+pub enum MatchOutcome {
+    Accepted(String),
+    Ambiguous(AmbiguousMatch),
+}
+
+/// Returned when no single candidate can be chosen automatically.
+#[derive(Debug, Clone)]
+pub struct AmbiguousMatch {
+    pub candidates: Vec<Candidate>,
+}
+
+/// Ranks `result.candidates` and decides whether the top hit can be accepted
+/// automatically or whether the caller needs to disambiguate.
+pub fn resolve(result: &EnrichResult) -> MatchOutcome {
+    let mut candidates = result.candidates.clone();
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let top = match candidates.first() {
+        Some(top) => top.clone(),
+        None => return MatchOutcome::Ambiguous(AmbiguousMatch { candidates }),
+    };
+    let runner_up_score = candidates.get(1).map(|c| c.score).unwrap_or(0.0);
+
+    if top.score >= AUTO_ACCEPT_THRESHOLD && top.score - runner_up_score >= AUTO_ACCEPT_MARGIN {
+        MatchOutcome::Accepted(top.mbid)
+    } else {
+        MatchOutcome::Ambiguous(AmbiguousMatch { candidates })
+    }
+}
+
+/// Scores a candidate release against the local query by combining
+/// normalized edit distance over `title`+`artist` with a popularity
+/// tie-breaker, both in `[0, 1]`.
+pub fn score_candidate(
+    query_title: &str,
+    query_artist: &str,
+    candidate_title: &str,
+    candidate_artist: &str,
+    popularity: f32,
+) -> f32 {
+    let query = format!("{query_artist} {query_title}").to_lowercase();
+    let candidate = format!("{candidate_artist} {candidate_title}").to_lowercase();
+
+    let distance = levenshtein(&query, &candidate) as f32;
+    let max_len = query.chars().count().max(candidate.chars().count()).max(1) as f32;
+    let similarity = 1.0 - (distance / max_len);
+
+    similarity * 0.9 + popularity.clamp(0.0, 1.0) * 0.1
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.iter().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let cost = if a_char == b_char { 0 } else { 1 };
+            let deletion = row[j] + 1;
+            let insertion = row[j + 1] + 1;
+            let substitution = prev + cost;
+            prev = row[j + 1];
+            row[j + 1] = deletion.min(insertion).min(substitution);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// A mutable reference to a local entity, borrowed long enough to apply a
+/// chosen match to it.
+pub enum EntityRef<'a> {
+    Artist(&'a mut Artist),
+    Album(&'a mut Album),
+    Song(&'a mut Song),
+}
+
+/// Rewrites `entity`'s id to the chosen MBID and stores the MBID in `tag`
+/// under the matching MusicBrainz item key. Only the in-memory `tag` is
+/// changed; nothing is written to the file, and the `From<&Tag>`
+/// conversions do not read that key, so they still derive the MD5 id.
+///
+/// Returns the entity's previous id so the caller can re-index anything
+/// that still references it (e.g. a `Song`/`Album` keyed under an
+/// `Artist`'s old MD5-derived id) rather than leaving a dangling reference.
+pub fn apply_match(entity: EntityRef, tag: &mut Tag, chosen_mbid: String) -> String {
+    let (old_id, musicbrainz_key) = match entity {
+        EntityRef::Artist(artist) => {
+            let old_id = artist.id.to_string();
+            artist.id = ArtistId::from(chosen_mbid.clone());
+            artist.musicbrainz_id = Some(chosen_mbid.clone());
+            (old_id, ItemKey::MusicBrainzArtistId)
+        }
+        EntityRef::Album(album) => {
+            let old_id = album.id.to_string();
+            album.id = AlbumId::from(chosen_mbid.clone());
+            album.musicbrainz_id = Some(chosen_mbid.clone());
+            (old_id, ItemKey::MusicBrainzReleaseId)
+        }
+        EntityRef::Song(song) => {
+            let old_id = song.id.to_string();
+            song.id = SongId::from(chosen_mbid.clone());
+            song.musicbrainz_id = Some(chosen_mbid.clone());
+            (old_id, ItemKey::MusicBrainzRecordingId)
+        }
+    };
+
+    tag.insert_item(TagItem::new(musicbrainz_key, ItemValue::Text(chosen_mbid)));
+    old_id
+}
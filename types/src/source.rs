@@ -0,0 +1,180 @@
+//! Federates search and playback across the local library and external
+//! catalogues.
+
+use async_trait::async_trait;
+
+use crate::ids::{AlbumId, ArtistId, CoverId, SongId};
+use crate::types::SimplifiedSong;
+
+/// A playable stream resolved from a `SourceEngine`.
+#[derive(Debug, Clone)]
+pub struct StreamHandle {
+    pub url: String,
+    pub mime_type: String,
+}
+
+/// An external (or local) catalogue the player can search and stream from.
+#[async_trait]
+pub trait SourceEngine: Send + Sync {
+    async fn search(&self, query: &str) -> Vec<SimplifiedSong>;
+    async fn resolve_stream(&self, song_id: &str) -> Option<StreamHandle>;
+}
+
+/// Holds the configured set of `SourceEngine`s, tried in order, so
+/// production code builds it from config while tests inject mocks.
+pub struct SourceRegistry {
+    engines: Vec<Box<dyn SourceEngine>>,
+}
+
+impl SourceRegistry {
+    pub fn new(engines: Vec<Box<dyn SourceEngine>>) -> Self {
+        SourceRegistry { engines }
+    }
+
+    /// Searches each engine in turn, returning the first non-empty result.
+    pub async fn search(&self, query: &str) -> Vec<SimplifiedSong> {
+        for engine in &self.engines {
+            let results = engine.search(query).await;
+            if !results.is_empty() {
+                return results;
+            }
+        }
+        Vec::new()
+    }
+}
+
+/// The artist part of a search query, so provider results can be checked
+/// against what the user actually asked for. The title part is not kept.
+pub(crate) struct ParsedQuery<'a> {
+    artist: &'a str,
+}
+
+impl<'a> ParsedQuery<'a> {
+    /// Splits `"artist - title"` style queries; falls back to matching the
+    /// whole query against the artist field when there's no separator.
+    pub(crate) fn parse(query: &'a str) -> Self {
+        match query.split_once(" - ") {
+            Some((artist, _title)) => ParsedQuery { artist },
+            None => ParsedQuery { artist: query },
+        }
+    }
+}
+
+pub(crate) fn artist_overlaps(query: &ParsedQuery, candidate_artist: &str) -> bool {
+    let query_artist = query.artist.to_lowercase();
+    let candidate_artist = candidate_artist.to_lowercase();
+    query_artist.contains(&candidate_artist) || candidate_artist.contains(&query_artist)
+}
+
+/// A single Invidious search hit, as returned by `GET /api/v1/search`.
+///
+/// Fields default rather than failing to deserialize, because the search
+/// endpoint's mixed result set can include channels/playlists that carry
+/// none of them; entries missing a `videoId` are filtered out afterward.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub(crate) struct InvidiousVideo {
+    #[serde(rename = "videoId", default)]
+    pub(crate) video_id: String,
+    #[serde(default)]
+    pub(crate) title: String,
+    #[serde(default)]
+    pub(crate) author: String,
+    #[serde(rename = "viewCount", default)]
+    pub(crate) view_count: u64,
+    #[serde(rename = "lengthSeconds", default)]
+    pub(crate) length_seconds: u64,
+}
+
+impl From<InvidiousVideo> for SimplifiedSong {
+    fn from(video: InvidiousVideo) -> Self {
+        SimplifiedSong {
+            // The video id is the only handle `resolve_stream` can use to
+            // fetch playback later, so it must survive unhashed.
+            id: SongId::from(video.video_id.clone()),
+            artist_id: ArtistId::from_md5(&video.author),
+            artist: video.author,
+            // Invidious results aren't grouped by album; file them together
+            // under a synthetic one rather than leaving album_id unset.
+            album: "YouTube".to_string(),
+            album_id: AlbumId::from_md5("youtube"),
+            title: video.title,
+            genre: String::new(),
+            cover: Some(CoverId::from(video.video_id)),
+            duration: std::time::Duration::from_secs(video.length_seconds),
+            musicbrainz_id: None,
+        }
+    }
+}
+
+/// Picks the best Invidious hit for `query`: most-viewed wins among results
+/// whose author plausibly matches the query's artist.
+pub(crate) fn best_match(
+    query: &ParsedQuery,
+    videos: Vec<InvidiousVideo>,
+) -> Option<InvidiousVideo> {
+    videos
+        .into_iter()
+        .filter(|video| artist_overlaps(query, &video.author))
+        .max_by_key(|video| video.view_count)
+}
+
+/// Resolves tracks missing from the local library against a configurable
+/// Invidious instance.
+pub struct InvidiousSourceEngine {
+    instance_url: String,
+    client: reqwest::Client,
+}
+
+impl InvidiousSourceEngine {
+    pub fn new(instance_url: impl Into<String>) -> Self {
+        InvidiousSourceEngine {
+            instance_url: instance_url.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn search_videos(&self, query: &str) -> Vec<InvidiousVideo> {
+        let url = format!("{}/api/v1/search", self.instance_url);
+        let response = match self
+            .client
+            .get(url)
+            .query(&[("q", query), ("type", "video")])
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(_) => return Vec::new(),
+        };
+
+        response
+            .json::<Vec<InvidiousVideo>>()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|video| !video.video_id.is_empty())
+            .collect()
+    }
+}
+
+#[async_trait]
+impl SourceEngine for InvidiousSourceEngine {
+    async fn search(&self, query: &str) -> Vec<SimplifiedSong> {
+        let parsed = ParsedQuery::parse(query);
+        let videos = self.search_videos(query).await;
+
+        best_match(&parsed, videos)
+            .into_iter()
+            .map(SimplifiedSong::from)
+            .collect()
+    }
+
+    async fn resolve_stream(&self, song_id: &str) -> Option<StreamHandle> {
+        Some(StreamHandle {
+            url: format!(
+                "{}/latest_version?id={}&itag=140",
+                self.instance_url, song_id
+            ),
+            mime_type: "audio/mp4".to_string(),
+        })
+    }
+}
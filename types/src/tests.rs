@@ -1,7 +1,12 @@
 use std::collections::HashMap;
+use std::sync::mpsc::TryRecvError;
 use std::time::Duration;
 
+use super::enrichment::*;
+use super::matching::*;
+use super::source::*;
 use super::types::*;
+use async_trait::async_trait;
 use lofty::{Accessor, ItemKey, ItemValue, Tag, TagItem, TagType};
 use mdns_sd::ServiceInfo;
 use tantivy::{
@@ -9,23 +14,53 @@ use tantivy::{
     Document,
 };
 
+/// Always returns the same search results, regardless of the query.
+struct StubSourceEngine {
+    results: Vec<SimplifiedSong>,
+}
+
+#[async_trait]
+impl SourceEngine for StubSourceEngine {
+    async fn search(&self, _query: &str) -> Vec<SimplifiedSong> {
+        self.results.clone()
+    }
+
+    async fn resolve_stream(&self, _song_id: &str) -> Option<StreamHandle> {
+        None
+    }
+}
+
+/// Always returns the same candidate list, regardless of the entity queried.
+struct StubMusicBrainzClient {
+    candidates: Vec<Candidate>,
+}
+
+impl MusicBrainzClient for StubMusicBrainzClient {
+    fn lookup(&self, _entity: &Entity) -> Vec<Candidate> {
+        self.candidates.clone()
+    }
+}
+
 #[test]
 fn document_to_artist() {
     let mut schema_builder: SchemaBuilder = Schema::builder();
 
     let id_field = schema_builder.add_text_field("id", STRING | STORED);
     let name_field = schema_builder.add_text_field("name", TEXT | STORED);
+    let musicbrainz_id_field = schema_builder.add_text_field("musicbrainz_id", STRING | STORED);
 
     schema_builder.build();
 
     let mut doc = Document::default();
     doc.add_text(id_field, "id");
     doc.add_text(name_field, "name");
+    doc.add_text(musicbrainz_id_field, "musicbrainz_id");
 
     let artist = Artist::from(doc);
 
     assert_eq!(artist.id, "id");
     assert_eq!(artist.name, "name");
+    assert_eq!(artist.musicbrainz_id, Some("musicbrainz_id".to_string()));
 }
 
 #[test]
@@ -37,6 +72,8 @@ fn document_to_album() {
     let artist_field = schema_builder.add_text_field("artist", TEXT | STORED);
     let year_field = schema_builder.add_i64_field("year", STORED);
     let cover_field = schema_builder.add_text_field("cover", STRING | STORED);
+    let artist_id_field = schema_builder.add_text_field("artist_id", STRING | STORED);
+    let musicbrainz_id_field = schema_builder.add_text_field("musicbrainz_id", STRING | STORED);
 
     schema_builder.build();
 
@@ -46,6 +83,8 @@ fn document_to_album() {
     doc.add_text(artist_field, "artist");
     doc.add_i64(year_field, 2020);
     doc.add_text(cover_field, "cover");
+    doc.add_text(artist_id_field, "artist_id");
+    doc.add_text(musicbrainz_id_field, "musicbrainz_id");
 
     let album = Album::from(doc);
 
@@ -53,7 +92,12 @@ fn document_to_album() {
     assert_eq!(album.title, "title");
     assert_eq!(album.artist, "artist");
     assert_eq!(album.year, Some(2020));
-    assert_eq!(album.cover, Some("cover".to_string()));
+    assert_eq!(album.cover, Some(CoverId::from("cover".to_string())));
+    assert_eq!(
+        album.artist_id,
+        Some(ArtistId::from("artist_id".to_string()))
+    );
+    assert_eq!(album.musicbrainz_id, Some("musicbrainz_id".to_string()));
 }
 
 #[test]
@@ -69,6 +113,7 @@ fn document_to_simplified_song() {
     let duration_field = schema_builder.add_i64_field("duration", STORED);
     let artist_id = schema_builder.add_text_field("artist_id", STRING | STORED);
     let album_id = schema_builder.add_text_field("album_id", STRING | STORED);
+    let musicbrainz_id_field = schema_builder.add_text_field("musicbrainz_id", STRING | STORED);
 
     schema_builder.build();
 
@@ -82,6 +127,7 @@ fn document_to_simplified_song() {
     doc.add_i64(duration_field, 100);
     doc.add_text(artist_id, "artist_id");
     doc.add_text(album_id, "album_id");
+    doc.add_text(musicbrainz_id_field, "musicbrainz_id");
 
     let song = SimplifiedSong::from(doc);
 
@@ -90,10 +136,11 @@ fn document_to_simplified_song() {
     assert_eq!(song.artist, "artist");
     assert_eq!(song.album, "album");
     assert_eq!(song.genre, "genre");
-    assert_eq!(song.cover, Some("cover".to_string()));
+    assert_eq!(song.cover, Some(CoverId::from("cover".to_string())));
     assert_eq!(song.duration, Duration::from_secs(100));
     assert_eq!(song.artist_id, "artist_id");
     assert_eq!(song.album_id, "album_id");
+    assert_eq!(song.musicbrainz_id, Some("musicbrainz_id".to_string()));
 }
 
 #[test]
@@ -156,7 +203,7 @@ fn tag_to_album() {
     assert_eq!(album.id, id);
     assert_eq!(album.title, "The Off-Season");
     assert_eq!(album.artist, "J. Cole");
-    assert_eq!(album.artist_id, artist_id);
+    assert_eq!(album.artist_id, artist_id.map(ArtistId::from));
 }
 
 #[test]
@@ -208,15 +255,9 @@ fn service_info_to_airplay_device() {
     let port: u16 = 7000;
     let properties: Option<HashMap<String, String>> = None;
 
-    let service_info = ServiceInfo::new(
-        service_type,
-        instance_name,
-        host_name,
-        ip,
-        port,
-        properties,
-    )
-    .expect("Failed to create ServiceInfo");
+    let service_info =
+        ServiceInfo::new(service_type, instance_name, host_name, ip, port, properties)
+            .expect("Failed to create ServiceInfo");
 
     let device = Device::from(service_info);
 
@@ -234,7 +275,420 @@ fn airplay_service_name_is_correct() {
     assert_eq!(AIRPLAY_SERVICE_NAME, "_raop._tcp.local.");
 }
 
+#[test]
+fn service_info_to_airplay_device_with_capabilities() {
+    let service_type = "_raop._tcp.local.";
+    let instance_name = "AABBCCDD11223344@Kitchen Speaker";
+    let host_name = "kitchen-speaker.local.";
+    let ip = "192.168.1.150";
+    let port: u16 = 7000;
+
+    let mut properties = HashMap::new();
+    properties.insert("cn".to_string(), "0,1".to_string());
+    properties.insert("et".to_string(), "0,1".to_string());
+    properties.insert("sr".to_string(), "44100".to_string());
+    properties.insert("ss".to_string(), "16".to_string());
+    properties.insert("tp".to_string(), "UDP".to_string());
+
+    let service_info = ServiceInfo::new(
+        service_type,
+        instance_name,
+        host_name,
+        ip,
+        port,
+        Some(properties),
+    )
+    .expect("Failed to create ServiceInfo");
+
+    let device = Device::from(service_info);
+
+    let capabilities = device
+        .capabilities
+        .expect("airplay device should have capabilities");
+    assert_eq!(capabilities.codecs, vec![Codec::Pcm, Codec::Alac]);
+    assert_eq!(
+        capabilities.encryption_types,
+        vec![EncryptionType::None, EncryptionType::Rsa]
+    );
+    assert_eq!(capabilities.sample_rate, 44100);
+    assert_eq!(capabilities.sample_size, 16);
+    assert_eq!(capabilities.transport, "UDP");
+}
+
+#[test]
+fn airplay_device_capabilities_default_without_properties() {
+    let service_type = "_raop._tcp.local.";
+    let instance_name = "AABBCCDD11223344@Kitchen Speaker";
+    let host_name = "kitchen-speaker.local.";
+    let ip = "192.168.1.150";
+    let port: u16 = 7000;
+    let properties: Option<HashMap<String, String>> = None;
+
+    let service_info =
+        ServiceInfo::new(service_type, instance_name, host_name, ip, port, properties)
+            .expect("Failed to create ServiceInfo");
+
+    let device = Device::from(service_info);
+
+    let capabilities = device
+        .capabilities
+        .expect("airplay device should have capabilities");
+    assert_eq!(capabilities, DeviceCapabilities::default());
+}
+
 #[test]
 fn chromecast_service_name_is_correct() {
     assert_eq!(CHROMECAST_SERVICE_NAME, "_googlecast._tcp.local.");
 }
+
+#[test]
+fn service_info_to_chromecast_device() {
+    let service_type = "_googlecast._tcp.local.";
+    let instance_name = "AABBCCDD11223344";
+    let host_name = "living-room-tv.local.";
+    let ip = "192.168.1.200";
+    let port: u16 = 8009;
+
+    let mut properties = HashMap::new();
+    properties.insert("fn".to_string(), "Living Room TV".to_string());
+    properties.insert("md".to_string(), "Chromecast".to_string());
+    properties.insert("id".to_string(), "aabbccdd11223344".to_string());
+    properties.insert("rs".to_string(), "Spotify".to_string());
+
+    let service_info = ServiceInfo::new(
+        service_type,
+        instance_name,
+        host_name,
+        ip,
+        port,
+        Some(properties),
+    )
+    .expect("Failed to create ServiceInfo");
+
+    let device = Device::from(service_info);
+
+    assert_eq!(device.app, "chromecast");
+    assert!(device.is_cast_device);
+    assert!(!device.is_source_device);
+    assert_eq!(device.ip, "192.168.1.200");
+    assert_eq!(device.port, 8009);
+    assert_eq!(device.name, "Living Room TV");
+    assert_eq!(device.model, Some("Chromecast".to_string()));
+    assert_eq!(device.device_id, Some("aabbccdd11223344".to_string()));
+    assert_eq!(device.status, Some("Spotify".to_string()));
+}
+
+#[test]
+fn chromecast_device_falls_back_to_instance_name_without_fn() {
+    let service_type = "_googlecast._tcp.local.";
+    let instance_name = "AABBCCDD11223344";
+    let host_name = "kitchen-display.local.";
+    let ip = "192.168.1.201";
+    let port: u16 = 8009;
+
+    let mut properties = HashMap::new();
+    properties.insert("md".to_string(), "Nest Hub".to_string());
+
+    let service_info = ServiceInfo::new(
+        service_type,
+        instance_name,
+        host_name,
+        ip,
+        port,
+        Some(properties),
+    )
+    .expect("Failed to create ServiceInfo");
+
+    let device = Device::from(service_info);
+
+    assert_eq!(device.app, "chromecast");
+    assert_eq!(device.name, instance_name);
+    assert_eq!(device.model, Some("Nest Hub".to_string()));
+}
+
+#[test]
+fn enrichment_daemon_resolves_best_candidate() {
+    let client = StubMusicBrainzClient {
+        candidates: vec![
+            Candidate {
+                mbid: "weak-match".to_string(),
+                score: 0.4,
+            },
+            Candidate {
+                mbid: "strong-match".to_string(),
+                score: 0.95,
+            },
+        ],
+    };
+    let daemon = EnrichmentDaemon::spawn(client);
+
+    let request_id = daemon.enrich(Entity::Artist(Artist {
+        id: ArtistId::from("local-id".to_string()),
+        name: "J. Cole".to_string(),
+        musicbrainz_id: None,
+    }));
+
+    let result = loop {
+        match daemon.try_recv() {
+            Ok(result) => break result,
+            Err(TryRecvError::Empty) => continue,
+            Err(TryRecvError::Disconnected) => panic!("daemon shut down before replying"),
+        }
+    };
+
+    assert_eq!(result.id, request_id);
+    assert_eq!(result.mbid, Some("strong-match".to_string()));
+    assert_eq!(result.candidates.len(), 2);
+    daemon.join().expect("daemon thread should exit cleanly");
+}
+
+#[test]
+fn enrichment_daemon_echoes_request_ids_for_requests_in_flight() {
+    let client = StubMusicBrainzClient { candidates: vec![] };
+    let daemon = EnrichmentDaemon::spawn(client);
+
+    let first_id = daemon.enrich(Entity::Artist(Artist {
+        id: ArtistId::from("first".to_string()),
+        name: "First Artist".to_string(),
+        musicbrainz_id: None,
+    }));
+    let second_id = daemon.enrich(Entity::Artist(Artist {
+        id: ArtistId::from("second".to_string()),
+        name: "Second Artist".to_string(),
+        musicbrainz_id: None,
+    }));
+
+    assert_ne!(first_id, second_id);
+
+    let mut seen_ids = vec![];
+    while seen_ids.len() < 2 {
+        match daemon.try_recv() {
+            Ok(result) => seen_ids.push(result.id),
+            Err(TryRecvError::Empty) => continue,
+            Err(TryRecvError::Disconnected) => panic!("daemon shut down before replying"),
+        }
+    }
+
+    seen_ids.sort();
+    assert_eq!(seen_ids, vec![first_id, second_id]);
+    daemon.join().expect("daemon thread should exit cleanly");
+}
+
+#[test]
+fn resolve_id_falls_back_to_local_id_without_a_match() {
+    let result = EnrichResult {
+        id: 0,
+        mbid: None,
+        score: 0.0,
+        candidates: vec![],
+    };
+
+    assert_eq!(resolve_id("local-id", &result), "local-id");
+}
+
+#[test]
+fn resolve_id_prefers_the_musicbrainz_id() {
+    let result = EnrichResult {
+        id: 0,
+        mbid: Some("canonical-id".to_string()),
+        score: 0.9,
+        candidates: vec![],
+    };
+
+    assert_eq!(resolve_id("local-id", &result), "canonical-id");
+}
+
+#[test]
+fn matching_resolve_auto_accepts_a_clear_winner() {
+    let result = EnrichResult {
+        id: 0,
+        mbid: None,
+        score: 0.0,
+        candidates: vec![
+            Candidate {
+                mbid: "clear-winner".to_string(),
+                score: 0.95,
+            },
+            Candidate {
+                mbid: "distant-runner-up".to_string(),
+                score: 0.3,
+            },
+        ],
+    };
+
+    match resolve(&result) {
+        MatchOutcome::Accepted(mbid) => assert_eq!(mbid, "clear-winner"),
+        MatchOutcome::Ambiguous(_) => panic!("expected an auto-accepted match"),
+    }
+}
+
+#[test]
+fn matching_resolve_is_ambiguous_when_candidates_are_close() {
+    let result = EnrichResult {
+        id: 0,
+        mbid: None,
+        score: 0.0,
+        candidates: vec![
+            Candidate {
+                mbid: "first".to_string(),
+                score: 0.9,
+            },
+            Candidate {
+                mbid: "second".to_string(),
+                score: 0.88,
+            },
+        ],
+    };
+
+    match resolve(&result) {
+        MatchOutcome::Ambiguous(ambiguous) => assert_eq!(ambiguous.candidates.len(), 2),
+        MatchOutcome::Accepted(_) => panic!("expected an ambiguous match"),
+    }
+}
+
+#[test]
+fn score_candidate_rewards_closer_matches() {
+    let exact = score_candidate(
+        "The Off-Season",
+        "J. Cole",
+        "The Off-Season",
+        "J. Cole",
+        0.5,
+    );
+    let unrelated = score_candidate("The Off-Season", "J. Cole", "Views", "Drake", 0.5);
+
+    assert!(exact > unrelated);
+}
+
+#[test]
+fn apply_match_rewrites_the_entitys_id_and_tags_it() {
+    let old_id = ArtistId::from_md5("J. Cole");
+    let mut artist = Artist {
+        id: old_id.clone(),
+        name: "J. Cole".to_string(),
+        musicbrainz_id: None,
+    };
+    let mut tag = Tag::new(TagType::ID3v2);
+
+    let returned_old_id = apply_match(
+        EntityRef::Artist(&mut artist),
+        &mut tag,
+        "chosen-mbid".to_string(),
+    );
+
+    assert_eq!(returned_old_id, old_id.to_string());
+    assert_eq!(artist.id, "chosen-mbid");
+    assert_eq!(artist.musicbrainz_id, Some("chosen-mbid".to_string()));
+    assert_eq!(
+        tag.get_string(&ItemKey::MusicBrainzArtistId),
+        Some("chosen-mbid")
+    );
+}
+
+#[test]
+fn parsed_query_splits_artist_and_title() {
+    let parsed = ParsedQuery::parse("J. Cole - The Climb Back");
+    assert!(artist_overlaps(&parsed, "J. Cole"));
+    assert!(!artist_overlaps(&parsed, "The Climb Back"));
+}
+
+#[test]
+fn parsed_query_falls_back_to_whole_query_without_a_separator() {
+    let parsed = ParsedQuery::parse("J. Cole");
+    assert!(artist_overlaps(&parsed, "J. Cole"));
+    assert!(!artist_overlaps(&parsed, "Drake"));
+}
+
+#[test]
+fn artist_overlaps_is_case_insensitive_and_substring_tolerant() {
+    let parsed = ParsedQuery::parse("j. cole - The Climb Back");
+    assert!(artist_overlaps(&parsed, "J. Cole"));
+    assert!(!artist_overlaps(&parsed, "Drake"));
+}
+
+#[test]
+fn best_match_prefers_a_matching_artist_over_a_higher_view_count() {
+    let parsed = ParsedQuery::parse("J. Cole - The Climb Back");
+    let videos = vec![
+        InvidiousVideo {
+            video_id: "unrelated".to_string(),
+            title: "Unrelated Hit".to_string(),
+            author: "Someone Else".to_string(),
+            view_count: 1_000_000,
+            length_seconds: 200,
+        },
+        InvidiousVideo {
+            video_id: "matching".to_string(),
+            title: "The Climb Back".to_string(),
+            author: "J. Cole".to_string(),
+            view_count: 100,
+            length_seconds: 210,
+        },
+    ];
+
+    let best = best_match(&parsed, videos).expect("expected a matching video");
+    assert_eq!(best.video_id, "matching");
+}
+
+#[test]
+fn best_match_picks_the_most_viewed_among_matching_artists() {
+    let parsed = ParsedQuery::parse("J. Cole - The Climb Back");
+    let videos = vec![
+        InvidiousVideo {
+            video_id: "fewer-views".to_string(),
+            title: "The Climb Back (live)".to_string(),
+            author: "J. Cole".to_string(),
+            view_count: 100,
+            length_seconds: 210,
+        },
+        InvidiousVideo {
+            video_id: "more-views".to_string(),
+            title: "The Climb Back".to_string(),
+            author: "J. Cole".to_string(),
+            view_count: 5_000,
+            length_seconds: 210,
+        },
+    ];
+
+    let best = best_match(&parsed, videos).expect("expected a matching video");
+    assert_eq!(best.video_id, "more-views");
+}
+
+#[test]
+fn simplified_song_from_invidious_video_keeps_the_literal_video_id() {
+    let video = InvidiousVideo {
+        video_id: "abc123".to_string(),
+        title: "The Climb Back".to_string(),
+        author: "J. Cole".to_string(),
+        view_count: 100,
+        length_seconds: 210,
+    };
+
+    let song = SimplifiedSong::from(video);
+
+    assert_eq!(song.id, "abc123");
+    assert_eq!(song.cover, Some(CoverId::from("abc123".to_string())));
+    assert_eq!(song.title, "The Climb Back");
+    assert_eq!(song.artist, "J. Cole");
+    assert_eq!(song.duration, Duration::from_secs(210));
+}
+
+#[tokio::test]
+async fn source_registry_returns_first_engines_non_empty_results() {
+    let empty_engine = StubSourceEngine { results: vec![] };
+    let song = SimplifiedSong::from(InvidiousVideo {
+        video_id: "abc123".to_string(),
+        title: "The Climb Back".to_string(),
+        author: "J. Cole".to_string(),
+        view_count: 100,
+        length_seconds: 210,
+    });
+    let matching_engine = StubSourceEngine {
+        results: vec![song.clone()],
+    };
+
+    let registry = SourceRegistry::new(vec![Box::new(empty_engine), Box::new(matching_engine)]);
+
+    let results = registry.search("J. Cole - The Climb Back").await;
+    assert_eq!(results, vec![song]);
+}
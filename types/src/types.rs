@@ -0,0 +1,451 @@
+use std::time::Duration;
+
+use lofty::{Accessor, ItemKey, Tag};
+use mdns_sd::ServiceInfo;
+use tantivy::{
+    schema::{Field, Schema, SchemaBuilder, Value, STORED, STRING, TEXT},
+    Document,
+};
+
+pub use crate::ids::{
+    AlbumId, AlbumIdRef, ArtistId, ArtistIdRef, CoverId, CoverIdRef, SongId, SongIdRef,
+};
+
+pub const AIRPLAY_SERVICE_NAME: &str = "_raop._tcp.local.";
+pub const CHROMECAST_SERVICE_NAME: &str = "_googlecast._tcp.local.";
+
+fn get_text(doc: &Document, field_id: u32) -> String {
+    doc.get_first(Field::from_field_id(field_id))
+        .and_then(Value::as_text)
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn get_opt_text(doc: &Document, field_id: u32) -> Option<String> {
+    doc.get_first(Field::from_field_id(field_id))
+        .and_then(Value::as_text)
+        .map(|s| s.to_string())
+}
+
+fn get_opt_i64(doc: &Document, field_id: u32) -> Option<i64> {
+    doc.get_first(Field::from_field_id(field_id))
+        .and_then(Value::as_i64)
+}
+
+fn get_id<T: From<String>>(doc: &Document, field_id: u32) -> T {
+    T::from(get_text(doc, field_id))
+}
+
+fn get_opt_id<T: From<String>>(doc: &Document, field_id: u32) -> Option<T> {
+    get_opt_text(doc, field_id).map(T::from)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artist {
+    pub id: ArtistId,
+    pub name: String,
+    pub musicbrainz_id: Option<String>,
+}
+
+/// Schema for the artist index, mirrored field-for-field by `From<Document>` below.
+pub fn artist_schema() -> Schema {
+    let mut builder: SchemaBuilder = Schema::builder();
+    builder.add_text_field("id", STRING | STORED);
+    builder.add_text_field("name", TEXT | STORED);
+    builder.add_text_field("musicbrainz_id", STRING | STORED);
+    builder.build()
+}
+
+impl From<Document> for Artist {
+    fn from(doc: Document) -> Self {
+        Artist {
+            id: get_id(&doc, 0),
+            name: get_text(&doc, 1),
+            musicbrainz_id: get_opt_text(&doc, 2),
+        }
+    }
+}
+
+impl From<&Tag> for Artist {
+    fn from(tag: &Tag) -> Self {
+        let name = tag
+            .get_string(&ItemKey::AlbumArtist)
+            .unwrap_or(tag.artist().unwrap_or("None"))
+            .to_string();
+        let id = ArtistId::from_md5(&name);
+
+        Artist {
+            id,
+            name,
+            musicbrainz_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Album {
+    pub id: AlbumId,
+    pub title: String,
+    pub artist: String,
+    pub artist_id: Option<ArtistId>,
+    pub year: Option<i64>,
+    pub cover: Option<CoverId>,
+    pub musicbrainz_id: Option<String>,
+}
+
+/// Schema for the album index, mirrored field-for-field by `From<Document>` below.
+pub fn album_schema() -> Schema {
+    let mut builder: SchemaBuilder = Schema::builder();
+    builder.add_text_field("id", STRING | STORED);
+    builder.add_text_field("title", TEXT | STORED);
+    builder.add_text_field("artist", TEXT | STORED);
+    builder.add_i64_field("year", STORED);
+    builder.add_text_field("cover", STRING | STORED);
+    builder.add_text_field("artist_id", STRING | STORED);
+    builder.add_text_field("musicbrainz_id", STRING | STORED);
+    builder.build()
+}
+
+impl From<Document> for Album {
+    fn from(doc: Document) -> Self {
+        Album {
+            id: get_id(&doc, 0),
+            title: get_text(&doc, 1),
+            artist: get_text(&doc, 2),
+            year: get_opt_i64(&doc, 3),
+            cover: get_opt_id(&doc, 4),
+            artist_id: get_opt_id(&doc, 5),
+            musicbrainz_id: get_opt_text(&doc, 6),
+        }
+    }
+}
+
+impl From<&Tag> for Album {
+    fn from(tag: &Tag) -> Self {
+        let artist = tag
+            .get_string(&ItemKey::AlbumArtist)
+            .unwrap_or(tag.artist().unwrap_or("None"))
+            .to_string();
+        let artist_id = Some(ArtistId::from_md5(&artist));
+
+        let title = tag
+            .get_string(&ItemKey::AlbumTitle)
+            .unwrap_or(tag.album().unwrap_or("None"))
+            .to_string();
+        let id = AlbumId::from_md5(&title);
+
+        Album {
+            id,
+            title,
+            artist,
+            artist_id,
+            year: tag.year().map(|year| year as i64),
+            cover: None,
+            musicbrainz_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimplifiedSong {
+    pub id: SongId,
+    pub title: String,
+    pub artist: String,
+    pub artist_id: ArtistId,
+    pub album: String,
+    pub album_id: AlbumId,
+    pub genre: String,
+    pub cover: Option<CoverId>,
+    pub duration: Duration,
+    pub musicbrainz_id: Option<String>,
+}
+
+/// Schema for the song index, mirrored field-for-field by `From<Document>` below.
+pub fn song_schema() -> Schema {
+    let mut builder: SchemaBuilder = Schema::builder();
+    builder.add_text_field("id", STRING | STORED);
+    builder.add_text_field("title", TEXT | STORED);
+    builder.add_text_field("artist", TEXT | STORED);
+    builder.add_text_field("album", TEXT | STORED);
+    builder.add_text_field("genre", TEXT);
+    builder.add_text_field("cover", STRING | STORED);
+    builder.add_i64_field("duration", STORED);
+    builder.add_text_field("artist_id", STRING | STORED);
+    builder.add_text_field("album_id", STRING | STORED);
+    builder.add_text_field("musicbrainz_id", STRING | STORED);
+    builder.build()
+}
+
+impl From<Document> for SimplifiedSong {
+    fn from(doc: Document) -> Self {
+        SimplifiedSong {
+            id: get_id(&doc, 0),
+            title: get_text(&doc, 1),
+            artist: get_text(&doc, 2),
+            album: get_text(&doc, 3),
+            genre: get_text(&doc, 4),
+            cover: get_opt_id(&doc, 5),
+            duration: Duration::from_secs(get_opt_i64(&doc, 6).unwrap_or_default() as u64),
+            artist_id: get_id(&doc, 7),
+            album_id: get_id(&doc, 8),
+            musicbrainz_id: get_opt_text(&doc, 9),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Song {
+    pub id: SongId,
+    pub title: String,
+    pub artist: String,
+    pub artist_id: ArtistId,
+    pub album: String,
+    pub album_id: AlbumId,
+    pub album_artist: String,
+    pub genre: String,
+    pub duration: Duration,
+    pub track: Option<u32>,
+    pub cover: Option<CoverId>,
+    pub musicbrainz_id: Option<String>,
+}
+
+impl From<&Tag> for Song {
+    fn from(tag: &Tag) -> Self {
+        let title = tag.title().unwrap_or("Unknown Title").to_string();
+        let artist = tag.artist().unwrap_or("Unknown Artist").to_string();
+        let album = tag.album().unwrap_or("Unknown Album").to_string();
+        let album_artist = tag
+            .get_string(&ItemKey::AlbumArtist)
+            .unwrap_or(&artist)
+            .to_string();
+        let genre = tag.genre().unwrap_or("Unknown Genre").to_string();
+
+        let artist_id = ArtistId::from_md5(&album_artist);
+        let album_id = AlbumId::from_md5(tag.get_string(&ItemKey::AlbumTitle).unwrap_or(&album));
+        let id = SongId::from_md5(&format!("{album_id}-{title}"));
+
+        Song {
+            id,
+            title,
+            artist,
+            artist_id,
+            album,
+            album_id,
+            album_artist,
+            genre,
+            // Populated from the decoded audio stream, not the tag, once the file is opened.
+            duration: Duration::from_secs(0),
+            track: tag.track(),
+            cover: None,
+            musicbrainz_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    pub name: String,
+    pub app: String,
+    pub ip: String,
+    pub port: u16,
+    pub is_cast_device: bool,
+    pub is_source_device: bool,
+    /// Model name, only populated for Chromecast devices (TXT key `md`).
+    pub model: Option<String>,
+    /// Device UUID, only populated for Chromecast devices (TXT key `id`).
+    pub device_id: Option<String>,
+    /// Currently running app/status, only populated for Chromecast devices (TXT key `rs`).
+    pub status: Option<String>,
+    /// Negotiable codec/encryption profile, only populated for AirPlay devices.
+    pub capabilities: Option<DeviceCapabilities>,
+}
+
+/// Reads a TXT record value by key, since `ServiceInfo` has no schema and
+/// every key is just an entry in its `properties` map.
+fn txt_value(info: &ServiceInfo, key: &str) -> Option<String> {
+    info.get_properties()
+        .get(key)
+        .map(|property| property.val_str().to_string())
+}
+
+/// An AirPlay codec, as advertised in the TXT `cn` list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Pcm,
+    Alac,
+    Aac,
+    AacEld,
+    Unknown(u8),
+}
+
+impl From<u8> for Codec {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Codec::Pcm,
+            1 => Codec::Alac,
+            2 => Codec::Aac,
+            3 => Codec::AacEld,
+            other => Codec::Unknown(other),
+        }
+    }
+}
+
+/// An AirPlay encryption type, as advertised in the TXT `et` list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionType {
+    None,
+    Rsa,
+    FairPlay,
+    MfiSap,
+    FairPlaySapAt25,
+    Unknown(u8),
+}
+
+impl From<u8> for EncryptionType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => EncryptionType::None,
+            1 => EncryptionType::Rsa,
+            3 => EncryptionType::FairPlay,
+            4 => EncryptionType::MfiSap,
+            5 => EncryptionType::FairPlaySapAt25,
+            other => EncryptionType::Unknown(other),
+        }
+    }
+}
+
+/// The stream profile an AirPlay receiver advertises via its TXT record, so
+/// the player can pick a codec/sample rate/encryption it actually supports
+/// before connecting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceCapabilities {
+    pub codecs: Vec<Codec>,
+    pub sample_rate: u32,
+    pub sample_size: u8,
+    pub encryption_types: Vec<EncryptionType>,
+    pub transport: String,
+}
+
+impl Default for DeviceCapabilities {
+    fn default() -> Self {
+        DeviceCapabilities {
+            codecs: vec![Codec::Pcm],
+            sample_rate: 44_100,
+            sample_size: 16,
+            encryption_types: vec![EncryptionType::None],
+            transport: "UDP".to_string(),
+        }
+    }
+}
+
+fn parse_csv<T: From<u8>>(value: &str) -> Vec<T> {
+    value
+        .split(',')
+        .filter_map(|entry| entry.trim().parse::<u8>().ok())
+        .map(T::from)
+        .collect()
+}
+
+impl DeviceCapabilities {
+    fn from_service_info(info: &ServiceInfo) -> Self {
+        let defaults = DeviceCapabilities::default();
+
+        let codecs = txt_value(info, "cn")
+            .map(|value| parse_csv(&value))
+            .filter(|codecs: &Vec<Codec>| !codecs.is_empty())
+            .unwrap_or(defaults.codecs);
+
+        let encryption_types = txt_value(info, "et")
+            .map(|value| parse_csv(&value))
+            .filter(|types: &Vec<EncryptionType>| !types.is_empty())
+            .unwrap_or(defaults.encryption_types);
+
+        let sample_rate = txt_value(info, "sr")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.sample_rate);
+
+        let sample_size = txt_value(info, "ss")
+            .or_else(|| txt_value(info, "sv"))
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.sample_size);
+
+        let transport = txt_value(info, "tp").unwrap_or(defaults.transport);
+
+        DeviceCapabilities {
+            codecs,
+            sample_rate,
+            sample_size,
+            encryption_types,
+            transport,
+        }
+    }
+}
+
+impl From<ServiceInfo> for Device {
+    fn from(info: ServiceInfo) -> Self {
+        let ip = info
+            .get_addresses()
+            .iter()
+            .next()
+            .map(|addr| addr.to_string())
+            .unwrap_or_default();
+        let port = info.get_port();
+        let service_type = info.get_type();
+
+        if service_type == AIRPLAY_SERVICE_NAME {
+            let fullname = info.get_fullname();
+            let name = fullname
+                .split('@')
+                .nth(1)
+                .and_then(|rest| rest.split('.').next())
+                .unwrap_or(fullname)
+                .to_string();
+
+            Device {
+                name,
+                app: "airplay".to_string(),
+                ip,
+                port,
+                is_cast_device: true,
+                is_source_device: false,
+                model: None,
+                device_id: None,
+                status: None,
+                capabilities: Some(DeviceCapabilities::from_service_info(&info)),
+            }
+        } else if service_type == CHROMECAST_SERVICE_NAME {
+            let fullname = info.get_fullname();
+            let instance_name = fullname
+                .strip_suffix(CHROMECAST_SERVICE_NAME)
+                .and_then(|rest| rest.strip_suffix('.'))
+                .unwrap_or(fullname);
+            let name = txt_value(&info, "fn").unwrap_or_else(|| instance_name.to_string());
+
+            Device {
+                name,
+                app: "chromecast".to_string(),
+                ip,
+                port,
+                is_cast_device: true,
+                is_source_device: false,
+                model: txt_value(&info, "md"),
+                device_id: txt_value(&info, "id"),
+                status: txt_value(&info, "rs"),
+                capabilities: None,
+            }
+        } else {
+            Device {
+                name: info.get_fullname().to_string(),
+                app: "unknown".to_string(),
+                ip,
+                port,
+                is_cast_device: false,
+                is_source_device: false,
+                model: None,
+                device_id: None,
+                status: None,
+                capabilities: None,
+            }
+        }
+    }
+}